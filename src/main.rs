@@ -29,10 +29,13 @@
 //!
 //! To run journaldriver on non-GCP machines, users must specify the
 //! `GOOGLE_APPLICATION_CREDENTIALS`, `GOOGLE_CLOUD_PROJECT` and
-//! `LOG_NAME` environment variables.
+//! `LOG_NAME` environment variables, as well as `LOG_STREAM` or `MONITORED_RESOURCE_TYPE`
+//! (one of `k8s_container`, `gae_app`, `generic_node` or `generic_task`,
+//! with labels sourced from the environment variables appropriate to
+//! that type - see `configured_resource`) to describe the monitored
+//! resource, since the metadata server is unreachable.
 
 #[macro_use] extern crate failure;
-#[macro_use] extern crate hyper;
 #[macro_use] extern crate log;
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate serde_json;
@@ -41,21 +44,22 @@
 extern crate chrono;
 extern crate env_logger;
 extern crate medallion;
-extern crate reqwest;
 extern crate serde;
 extern crate systemd;
+extern crate ureq;
 
 use chrono::offset::LocalResult;
 use chrono::prelude::*;
 use failure::ResultExt;
-use reqwest::{header, Client};
 use serde_json::Value;
 use std::env;
+use std::fmt;
 use std::fs::{self, File};
 use std::io::{self, Read, ErrorKind, Write};
 use std::mem;
 use std::path::PathBuf;
 use std::process;
+use std::thread;
 use std::time::{Duration, Instant};
 use systemd::journal::*;
 
@@ -73,7 +77,8 @@ const METADATA_PROJECT_URL: &str = "http://metadata.google.internal/computeMetad
 // calls:
 //
 // https://cloud.google.com/compute/docs/storing-retrieving-metadata#querying
-header! { (MetadataFlavor, "Metadata-Flavor") => [String] }
+const METADATA_FLAVOR_HEADER: &str = "Metadata-Flavor";
+const METADATA_FLAVOR_VALUE: &str = "Google";
 
 /// Convenience type alias for results using failure's `Error` type.
 type Result<T> = std::result::Result<T, failure::Error>;
@@ -92,16 +97,6 @@ struct Credentials {
 }
 
 lazy_static! {
-    /// HTTP client instance preconfigured with the metadata header
-    /// required by Google.
-    static ref METADATA_CLIENT: Client = {
-        let mut headers = header::Headers::new();
-        headers.set(MetadataFlavor("Google".into()));
-
-        Client::builder().default_headers(headers)
-            .build().expect("Could not create metadata client")
-    };
-
     /// ID of the GCP project to which to send logs.
     static ref PROJECT_ID: String = get_project_id();
 
@@ -126,16 +121,25 @@ lazy_static! {
     static ref POSITION_FILE: PathBuf = env::var("CURSOR_POSITION_FILE")
         .unwrap_or("/var/lib/journaldriver/cursor.pos".into())
         .into();
+
+    /// Extra journal fields (on top of `LABEL_FIELDS`) to fold into
+    /// each entry's labels, configured as a comma-separated list of
+    /// field names.
+    static ref EXTRA_LABEL_FIELDS: Vec<String> = env::var("EXTRA_LABEL_FIELDS")
+        .map(|fields| fields.split(',')
+             .map(|field| field.trim().to_string())
+             .filter(|field| !field.is_empty())
+             .collect())
+        .unwrap_or_default();
 }
 
 /// Convenience helper for retrieving values from the metadata server.
 fn get_metadata(url: &str) -> Result<String> {
-    let mut output = String::new();
-    METADATA_CLIENT.get(url).send()?
-        .error_for_status()?
-        .read_to_string(&mut output)?;
+    let response = ureq::get(url)
+        .set(METADATA_FLAVOR_HEADER, METADATA_FLAVOR_VALUE)
+        .call()?;
 
-    Ok(output.trim().into())
+    Ok(response.into_string()?.trim().into())
 }
 
 /// Convenience helper for determining the project ID.
@@ -146,37 +150,91 @@ fn get_project_id() -> String {
         .expect("Could not determine project ID")
 }
 
+/// Builds a MonitoredResource (see
+/// https://cloud.google.com/logging/docs/api/v2/resource-list#resource-types)
+/// for `resource_type`, as selected by `MONITORED_RESOURCE_TYPE`, for
+/// hosts that can't reach the GCP metadata server at all (containers,
+/// AWS, bare metal, on-prem).
+///
+/// Labels are sourced from the environment variables appropriate to
+/// each resource type; any that aren't set default to the empty
+/// string, since Stackdriver accepts that.
+fn configured_resource(resource_type: &str) -> Value {
+    let labels = match resource_type {
+        "k8s_container" => json!({
+            "project_id": PROJECT_ID.as_str(),
+            "location": env::var("LOCATION").unwrap_or_default(),
+            "cluster_name": env::var("CLUSTER_NAME").unwrap_or_default(),
+            "namespace_name": env::var("POD_NAMESPACE").unwrap_or_default(),
+            "pod_name": env::var("POD_NAME").unwrap_or_default(),
+            "container_name": env::var("CONTAINER_NAME").unwrap_or_default(),
+        }),
+        "gae_app" => json!({
+            "project_id": PROJECT_ID.as_str(),
+            "module_id": env::var("GAE_MODULE_ID").unwrap_or_default(),
+            "version_id": env::var("GAE_VERSION").unwrap_or_default(),
+            "zone": env::var("ZONE").unwrap_or_default(),
+        }),
+        "generic_node" => json!({
+            "project_id": PROJECT_ID.as_str(),
+            "location": env::var("RESOURCE_LOCATION").unwrap_or_default(),
+            "namespace": env::var("RESOURCE_NAMESPACE").unwrap_or_default(),
+            "node_id": env::var("RESOURCE_NODE_ID").unwrap_or_default(),
+        }),
+        "generic_task" => json!({
+            "project_id": PROJECT_ID.as_str(),
+            "location": env::var("RESOURCE_LOCATION").unwrap_or_default(),
+            "namespace": env::var("RESOURCE_NAMESPACE").unwrap_or_default(),
+            "job": env::var("RESOURCE_JOB").unwrap_or_default(),
+            "task_id": env::var("RESOURCE_TASK_ID").unwrap_or_default(),
+        }),
+        // Anything else the user asks for is treated as a
+        // generic_node, the loosest of Stackdriver's generic types.
+        _ => return configured_resource("generic_node"),
+    };
+
+    json!({ "type": resource_type, "labels": labels })
+}
+
 /// Determines the monitored resource descriptor used in Stackdriver
 /// logs. On GCP this will be set to the instance ID as returned by
 /// the metadata server.
 ///
-/// On non-GCP machines the value is determined by using the
-/// `GOOGLE_CLOUD_PROJECT` and `LOG_NAME` environment variables.
+/// On non-GCP machines (containers, other clouds, bare metal) the
+/// value is determined by the `LOG_STREAM` or `MONITORED_RESOURCE_TYPE`
+/// environment variables, together with `GOOGLE_CLOUD_PROJECT` and
+/// `LOG_NAME`. GCE metadata is only probed once neither is set, since
+/// that's the only path that requires reaching
+/// metadata.google.internal.
 fn determine_monitored_resource() -> Value {
     if let Ok(log) = env::var("LOG_STREAM") {
-        json!({
+        return json!({
             "type": "logging_log",
             "labels": {
                 "project_id": PROJECT_ID.as_str(),
                 "name": log,
             }
-        })
-    } else {
-        let instance_id = get_metadata(METADATA_ID_URL)
-            .expect("Could not determine instance ID");
-
-        let zone = get_metadata(METADATA_ZONE_URL)
-            .expect("Could not determine instance zone");
+        });
+    }
 
-        json!({
-            "type": "gce_instance",
-            "labels": {
-                "project_id": PROJECT_ID.as_str(),
-                "instance_id": instance_id,
-                "zone": zone,
-            }
-        })
+    if let Ok(resource_type) = env::var("MONITORED_RESOURCE_TYPE") {
+        return configured_resource(&resource_type);
     }
+
+    let instance_id = get_metadata(METADATA_ID_URL)
+        .expect("Could not determine instance ID");
+
+    let zone = get_metadata(METADATA_ZONE_URL)
+        .expect("Could not determine instance zone");
+
+    json!({
+        "type": "gce_instance",
+        "labels": {
+            "project_id": PROJECT_ID.as_str(),
+            "instance_id": instance_id,
+            "zone": zone,
+        }
+    })
 }
 
 /// Represents the response returned by the metadata server's token
@@ -205,9 +263,10 @@ impl Token {
 /// Retrieves a token from the GCP metadata service. Retrieving these
 /// tokens requires no additional authentication.
 fn get_metadata_token() -> Result<Token> {
-    let token: TokenResponse  = METADATA_CLIENT
-        .get(METADATA_TOKEN_URL)
-        .send()?.json()?;
+    let token: TokenResponse = ureq::get(METADATA_TOKEN_URL)
+        .set(METADATA_FLAVOR_HEADER, METADATA_FLAVOR_VALUE)
+        .call()?
+        .into_json()?;
 
     debug!("Fetched new token from metadata service");
 
@@ -319,6 +378,50 @@ fn message_to_payload(message: Option<String>) -> Payload {
     }
 }
 
+/// Journal fields folded into the JSON payload alongside the message.
+///
+/// Unit, host, PID and friends are deliberately left out here: they're
+/// already promoted to Stackdriver `labels`/`operation` in
+/// `LogEntry::from`, which is both the more idiomatic place for
+/// low-cardinality, filterable metadata and where Stackdriver's UI
+/// looks for it. Keeping them here too would just double their
+/// storage cost on every entry. What's left is detail that's useful
+/// alongside the message but not worth promoting to a label.
+const PAYLOAD_METADATA_FIELDS: &[&str] = &[
+    "_CMDLINE",
+    "_EXE",
+    "CODE_FILE",
+    "CODE_LINE",
+    "CODE_FUNC",
+];
+
+/// Folds any `PAYLOAD_METADATA_FIELDS` present on `record` into
+/// `payload`, promoting a `TextPayload` to a `JsonPayload` (keyed as
+/// `message`) if any metadata is found. Entries with none of these
+/// fields are returned untouched.
+fn enrich_payload(payload: Payload, record: &JournalRecord) -> Payload {
+    let metadata: Vec<(String, Value)> = PAYLOAD_METADATA_FIELDS.iter()
+        .filter_map(|&field| record.get(field).map(|value| (field.to_lowercase(), json!(value))))
+        .collect();
+
+    if metadata.is_empty() {
+        return payload;
+    }
+
+    let mut json_payload = match payload {
+        Payload::JsonPayload { json_payload } => json_payload,
+        Payload::TextPayload { text_payload } => json!({ "message": text_payload }),
+    };
+
+    if let Value::Object(ref mut map) = json_payload {
+        for (key, value) in metadata {
+            map.entry(key).or_insert(value);
+        }
+    }
+
+    Payload::JsonPayload { json_payload }
+}
+
 /// Attempt to parse journald's microsecond timestamps into a UTC
 /// timestamp.
 ///
@@ -341,25 +444,35 @@ fn parse_microseconds(input: String) -> Option<DateTime<Utc>> {
 
 /// Converts a journald log message priority (using levels 0/emerg through
 /// 7/debug, see "man journalctl" and "man systemd.journal-fields") to a
-/// Stackdriver-compatible severity number (see
+/// Stackdriver `LogSeverity` name (see
 /// https://cloud.google.com/logging/docs/reference/v2/rest/v2/LogEntry#LogSeverity).
-/// Conveniently, the names are the same. Inconveniently, the numbers are not.
 ///
-/// Any unknown values are returned as an empty option.
-fn priority_to_severity(priority: String) -> Option<u32> {
-    match priority.as_ref() {
-        "0" => Some(800), // emerg
-        "1" => Some(700), // alert
-        "2" => Some(600), // crit
-        "3" => Some(500), // err
-        "4" => Some(400), // warning
-        "5" => Some(300), // notice
-        "6" => Some(200), // info
-        "7" => Some(100), // debug
-        _ => None,
+/// Entries with a missing or unparseable priority fall back to
+/// `DEFAULT`, which is itself a valid `LogSeverity`.
+fn priority_to_severity(priority: &str) -> &'static str {
+    match priority {
+        "0" => "EMERGENCY",
+        "1" => "ALERT",
+        "2" => "CRITICAL",
+        "3" => "ERROR",
+        "4" => "WARNING",
+        "5" => "NOTICE",
+        "6" => "INFO",
+        "7" => "DEBUG",
+        _ => "DEFAULT",
     }
 }
 
+/// Journal fields (besides `_HOSTNAME`/`_SYSTEMD_UNIT`, which are
+/// always present as `host`/`unit`) folded into each entry's labels.
+/// See `EXTRA_LABEL_FIELDS` for adding more via configuration.
+const LABEL_FIELDS: &[&str] = &[
+    "SYSLOG_IDENTIFIER",
+    "_COMM",
+    "_BOOT_ID",
+    "_TRANSPORT",
+];
+
 /// This structure represents a log entry in the format expected by
 /// the Stackdriver API.
 #[derive(Debug, Serialize)]
@@ -367,15 +480,22 @@ fn priority_to_severity(priority: String) -> Option<u32> {
 struct LogEntry {
     labels: Value,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    timestamp: Option<DateTime<Utc>>,
+    timestamp: DateTime<Utc>,
 
     #[serde(flatten)]
     payload: Payload,
 
     // https://cloud.google.com/logging/docs/reference/v2/rest/v2/LogEntry#LogSeverity
+    severity: &'static str,
+
+    /// A unique identifier for this entry, used by Stackdriver to
+    /// suppress duplicate writes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    insert_id: Option<String>,
+
+    /// Identifies the larger operation this entry is part of.
     #[serde(skip_serializing_if = "Option::is_none")]
-    severity: Option<u32>,
+    operation: Option<Value>,
 }
 
 impl From<JournalRecord> for LogEntry {
@@ -387,7 +507,11 @@ impl From<JournalRecord> for LogEntry {
         // The message field is technically just a convention, but
         // journald seems to default to it when ingesting unit
         // output.
-        let payload = message_to_payload(record.remove("MESSAGE"));
+        //
+        // Fold in source-level detail (originating binary, code
+        // location, ...) that's useful next to the message but isn't
+        // worth promoting to a label - see PAYLOAD_METADATA_FIELDS.
+        let payload = enrich_payload(message_to_payload(record.remove("MESSAGE")), &record);
 
         // Presumably this is always set, but who can be sure
         // about anything in this world.
@@ -397,37 +521,69 @@ impl From<JournalRecord> for LogEntry {
         // present on all others.
         let unit = record.remove("_SYSTEMD_UNIT");
 
-        // The source timestamp (if present) is specified in
-        // microseconds since epoch.
+        // The source timestamp is specified in microseconds since
+        // epoch. `_SOURCE_REALTIME_TIMESTAMP` (when the sending
+        // application generated the message) is preferred over
+        // `__REALTIME_TIMESTAMP` (when journald received it), since
+        // it is closer to the actual event.
         //
-        // If it is not present or can not be parsed, journaldriver
-        // will not send a timestamp for the log entry and it will
-        // default to the ingestion time.
+        // If neither is present or parseable, fall back to the
+        // moment journaldriver read the entry rather than leaving
+        // Stackdriver to guess at ingestion time.
         let timestamp = record
             .remove("_SOURCE_REALTIME_TIMESTAMP")
-            .and_then(parse_microseconds);
+            .and_then(parse_microseconds)
+            .or_else(|| record.remove("__REALTIME_TIMESTAMP").and_then(parse_microseconds))
+            .unwrap_or_else(Utc::now);
 
         // Journald uses syslogd's concept of priority. No idea if this is
-        // always present, but it's optional in the Stackdriver API, so we just
-        // omit it if we can't find or parse it.
+        // always present, so entries without a recognised priority are
+        // tagged with Stackdriver's own `DEFAULT` severity.
         let severity = record
             .remove("PRIORITY")
-            .and_then(priority_to_severity);
+            .map_or("DEFAULT", |p| priority_to_severity(&p));
+
+        // _PID identifies the originating process, which is the
+        // closest thing journald has to Stackdriver's notion of the
+        // "operation" an entry is part of.
+        let operation = record.remove("_PID")
+            .map(|pid| json!({ "id": pid, "producer": "journaldriver" }));
+
+        // MESSAGE_ID, when an application sets it, is journald's own
+        // concept of a unique identifier for a log event - exactly
+        // what Stackdriver's insertId is for.
+        let insert_id = record.remove("MESSAGE_ID");
+
+        let mut labels = json!({
+            "host": hostname,
+            "unit": unit.unwrap_or_else(|| "syslog".into()),
+        });
+
+        if let Value::Object(ref mut map) = labels {
+            let configured_fields = LABEL_FIELDS.iter().cloned()
+                .chain(EXTRA_LABEL_FIELDS.iter().map(String::as_str));
+
+            for field in configured_fields {
+                if let Some(value) = record.remove(field) {
+                    map.insert(field.to_lowercase(), Value::String(value));
+                }
+            }
+        }
 
         LogEntry {
             payload,
             timestamp,
-            labels: json!({
-                "host": hostname,
-                "unit": unit.unwrap_or_else(|| "syslog".into()),
-            }),
+            labels,
             severity,
+            insert_id,
+            operation,
         }
     }
 }
 
 /// Attempt to read from the journal. If no new entry is present,
-/// await the next one up to the specified timeout.
+/// block (via `sd_journal_wait` under `await_next_record`) for up to
+/// the specified timeout rather than spinning.
 fn receive_next_record(timeout: Duration, journal: &mut Journal)
                        -> Result<Option<JournalRecord>> {
     let next_record = journal.next_record()?;
@@ -441,31 +597,37 @@ fn receive_next_record(timeout: Duration, journal: &mut Journal)
 /// This function starts a double-looped, blocking receiver. It will
 /// buffer messages for half a second before flushing them to
 /// Stackdriver.
+///
+/// The inner loop blocks on `sd_journal_wait` (via
+/// `receive_next_record`) for whatever time remains in the current
+/// half-second window, rather than busy-polling, which keeps idle CPU
+/// usage negligible on quiet systems.
 fn receiver_loop(mut journal: Journal) -> Result<()> {
     let mut token = get_token()?;
-    let client = reqwest::Client::new();
 
     let mut buf: Vec<LogEntry> = Vec::new();
+    let mut cursors: Vec<String> = Vec::new();
     let iteration = Duration::from_millis(500);
 
     loop {
         trace!("Beginning outer iteration");
         let now = Instant::now();
 
-        loop {
-            if now.elapsed() > iteration {
-                break;
-            }
-
-            if let Ok(Some(entry)) = receive_next_record(iteration, &mut journal) {
+        while let Some(remaining) = iteration.checked_sub(now.elapsed()) {
+            if let Ok(Some(entry)) = receive_next_record(remaining, &mut journal) {
                 trace!("Received a new entry");
                 buf.push(entry.into());
+                // Captured right after reading the entry, so it can
+                // be persisted once (and only once) Stackdriver has
+                // actually accepted this specific entry - see flush().
+                cursors.push(journal.cursor()?);
             }
         }
 
         if !buf.is_empty() {
             let to_flush = mem::replace(&mut buf, Vec::new());
-            flush(&client, &mut token, to_flush, journal.cursor()?)?;
+            let to_flush_cursors = mem::replace(&mut cursors, Vec::new());
+            flush(&mut token, to_flush, to_flush_cursors)?;
         }
 
         trace!("Done outer iteration");
@@ -485,28 +647,51 @@ fn persist_cursor(cursor: String) -> Result<()> {
 /// In some cases large payloads seem to cause errors in Stackdriver -
 /// the chunks are therefore made smaller here.
 ///
-/// If flushing is successful the last cursor position will be
-/// persisted to disk.
-fn flush(client: &Client,
-         token: &mut Token,
+/// `cursors[i]` is the journal cursor position immediately after
+/// reading `entries[i]`. Since Stackdriver may reject some chunks
+/// while accepting others, the cursor is only ever advanced as far as
+/// the last chunk that was actually delivered (or permanently
+/// dropped) - a chunk that's still pending after exhausting retries
+/// stops cursor advancement entirely, so it and everything after it
+/// gets redelivered (not lost) on the next restart.
+///
+/// Rate-limiting and token expiry are retried rather than dropped;
+/// see `write_entries` and `write_chunk_with_retry` for how those are
+/// told apart from genuine payload rejections.
+fn flush(token: &mut Token,
          entries: Vec<LogEntry>,
-         cursor: String) -> Result<()> {
+         cursors: Vec<String>) -> Result<()> {
     if token.is_expired() {
         debug!("Refreshing Google metadata access token");
         let new_token = get_token()?;
         mem::replace(token, new_token);
     }
 
-    for chunk in entries.chunks(750) {
+    let mut confirmed_cursor: Option<String> = None;
+
+    for (chunk, chunk_cursors) in entries.chunks(750).zip(cursors.chunks(750)) {
         let request = prepare_request(chunk);
-        if let Err(write_error) = write_entries(client, token, request) {
-            error!("Failed to write {} entries: {}", chunk.len(), write_error)
-        } else {
-            debug!("Wrote {} entries to Stackdriver", chunk.len())
+
+        match write_chunk_with_retry(token, &request) {
+            WriteOutcome::Success => {
+                debug!("Wrote {} entries to Stackdriver", chunk.len());
+                confirmed_cursor = chunk_cursors.last().cloned();
+            },
+            WriteOutcome::Dropped(err) => {
+                error!("Dropping {} entries after a permanent Stackdriver error: {}", chunk.len(), err);
+                confirmed_cursor = chunk_cursors.last().cloned();
+            },
+            WriteOutcome::Failed(err) => {
+                error!("Giving up on {} entries for now after a transient Stackdriver error: {}", chunk.len(), err);
+                break;
+            },
         }
     }
 
-    persist_cursor(cursor)
+    match confirmed_cursor {
+        Some(cursor) => persist_cursor(cursor),
+        None => Ok(()),
+    }
 }
 
 /// Convert a slice of log entries into the format expected by
@@ -522,18 +707,111 @@ fn prepare_request(entries: &[LogEntry]) -> Value {
     })
 }
 
+/// Error from attempting to write a chunk to Stackdriver, classified
+/// by whether retrying could plausibly help.
+enum WriteError {
+    /// Stackdriver rejected the payload itself (400/404/413) -
+    /// resending the exact same request would just fail again.
+    Permanent(failure::Error),
+
+    /// A transport error or any other error status from Stackdriver
+    /// (rate-limiting (429), a request timeout (408), a 5xx response,
+    /// ...) - worth retrying as-is.
+    Transient(failure::Error),
+
+    /// The access token was rejected (401/403) - worth retrying, but
+    /// only after fetching a fresh token, since resending the same
+    /// one would just fail again too.
+    Unauthorized(failure::Error),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::Permanent(err)
+                | WriteError::Transient(err)
+                | WriteError::Unauthorized(err) => write!(f, "{}", err),
+        }
+    }
+}
+
 /// Perform the log entry insertion in Stackdriver Logging.
-fn write_entries(client: &Client, token: &Token, request: Value) -> Result<()> {
-    let mut response = client.post(ENTRIES_WRITE_URL)
-        .header(header::Authorization(format!("Bearer {}", token.token)))
-        .json(&request)
-        .send()?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let body = response.text().unwrap_or_else(|_| "no response body".into());
-        bail!("{} ({})", body, response.status())
+fn write_entries(token: &Token, request: &Value) -> std::result::Result<(), WriteError> {
+    let result = ureq::post(ENTRIES_WRITE_URL)
+        .set("Authorization", &format!("Bearer {}", token.token))
+        .send_json(request);
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_else(|_| "no response body".into());
+            let err = format_err!("{} ({})", body, code);
+
+            match code {
+                // Genuine payload rejections: the request itself is
+                // bad and retrying it unchanged won't help.
+                400 | 404 | 413 => Err(WriteError::Permanent(err)),
+                // The token was rejected; a fresh one might not be.
+                401 | 403 => Err(WriteError::Unauthorized(err)),
+                // Everything else, e.g. rate-limited (429), a request
+                // timeout (408) or a 5xx: worth trying again, possibly
+                // after backing off.
+                _ => Err(WriteError::Transient(err)),
+            }
+        },
+        Err(err) => Err(WriteError::Transient(err.into())),
+    }
+}
+
+/// Outcome of writing a single chunk, after retries.
+enum WriteOutcome {
+    Success,
+    Dropped(failure::Error),
+    Failed(failure::Error),
+}
+
+/// Maximum number of attempts (including the first) made against a
+/// chunk before giving up on a transient error.
+const MAX_WRITE_ATTEMPTS: u32 = 5;
+
+/// Writes a chunk, retrying transient failures with a bounded
+/// exponential backoff (1s, 2s, 4s, ...). A permanent error is
+/// surfaced immediately, without retrying.
+///
+/// If the token is rejected mid-flush, a fresh one is fetched before
+/// retrying - `flush` only refreshes it proactively once per call, so
+/// without this a token that expires partway through a large flush
+/// would otherwise sink every remaining chunk as a permanent failure.
+fn write_chunk_with_retry(token: &mut Token, request: &Value) -> WriteOutcome {
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        match write_entries(token, request) {
+            Ok(()) => return WriteOutcome::Success,
+            Err(WriteError::Permanent(err)) => return WriteOutcome::Dropped(err),
+            Err(WriteError::Unauthorized(err)) => {
+                if attempt >= MAX_WRITE_ATTEMPTS {
+                    return WriteOutcome::Failed(err);
+                }
+
+                warn!("Stackdriver rejected our access token, fetching a new one: {}", err);
+                match get_token() {
+                    Ok(new_token) => { mem::replace(token, new_token); },
+                    Err(err) => error!("Failed to refresh access token: {}", err),
+                }
+            },
+            Err(WriteError::Transient(err)) => {
+                if attempt >= MAX_WRITE_ATTEMPTS {
+                    return WriteOutcome::Failed(err);
+                }
+
+                let backoff = Duration::from_secs(1 << (attempt - 1));
+                warn!("Retrying Stackdriver write in {:?} after a transient error: {}", backoff, err);
+                thread::sleep(backoff);
+            },
+        }
     }
 }
 
@@ -3,16 +3,21 @@ use serde_json::to_string;
 
 #[test]
 fn test_text_entry_serialization() {
+    let timestamp: DateTime<Utc> = "2018-06-16T18:52:29.291187Z"
+        .to_string().parse().unwrap();
+
     let entry = LogEntry {
         labels: Value::Null,
-        timestamp: None,
+        timestamp,
         payload: Payload::TextPayload {
             text_payload: "test entry".into(),
         },
-        severity: None,
+        severity: "DEFAULT",
+        insert_id: None,
+        operation: None,
     };
 
-    let expected = "{\"labels\":null,\"textPayload\":\"test entry\"}";
+    let expected = "{\"labels\":null,\"timestamp\":\"2018-06-16T18:52:29.291187Z\",\"textPayload\":\"test entry\",\"severity\":\"DEFAULT\"}";
     let result = to_string(&entry).expect("serialization failed");
 
     assert_eq!(expected, result, "Plain text payload should serialize correctly")
@@ -20,18 +25,23 @@ fn test_text_entry_serialization() {
 
 #[test]
 fn test_json_entry_serialization() {
+    let timestamp: DateTime<Utc> = "2018-06-16T18:52:29.291187Z"
+        .to_string().parse().unwrap();
+
     let entry = LogEntry {
         labels: Value::Null,
-        timestamp: None,
+        timestamp,
         payload: Payload::JsonPayload {
             json_payload: json!({
                 "message": "JSON test"
             })
         },
-        severity: None,
+        severity: "DEFAULT",
+        insert_id: None,
+        operation: None,
     };
 
-    let expected = "{\"labels\":null,\"jsonPayload\":{\"message\":\"JSON test\"}}";
+    let expected = "{\"labels\":null,\"timestamp\":\"2018-06-16T18:52:29.291187Z\",\"jsonPayload\":{\"message\":\"JSON test\"},\"severity\":\"DEFAULT\"}";
     let result = to_string(&entry).expect("serialization failed");
 
     assert_eq!(expected, result, "JSOn payload should serialize correctly")
@@ -93,3 +103,52 @@ fn test_parse_microseconds() {
 
     assert_eq!(Some(expected), parse_microseconds(input));
 }
+
+#[test]
+fn test_priority_to_severity() {
+    let cases = [
+        ("0", "EMERGENCY"),
+        ("1", "ALERT"),
+        ("2", "CRITICAL"),
+        ("3", "ERROR"),
+        ("4", "WARNING"),
+        ("5", "NOTICE"),
+        ("6", "INFO"),
+        ("7", "DEBUG"),
+        ("8", "DEFAULT"),
+        ("not-a-priority", "DEFAULT"),
+    ];
+
+    for (priority, severity) in &cases {
+        assert_eq!(*severity, priority_to_severity(priority),
+                   "priority {} should map to {}", priority, severity);
+    }
+}
+
+#[test]
+fn test_enrich_payload_promotes_text_to_json() {
+    let mut record = JournalRecord::new();
+    record.insert("CODE_FILE".into(), "main.rs".into());
+
+    let payload = message_to_payload(Some("plain text payload".into()));
+    let enriched = enrich_payload(payload, &record);
+
+    let expected = Payload::JsonPayload {
+        json_payload: json!({
+            "message": "plain text payload",
+            "code_file": "main.rs",
+        })
+    };
+
+    assert_eq!(expected, enriched, "a text payload with metadata should be promoted to JSON");
+}
+
+#[test]
+fn test_enrich_payload_without_metadata_is_untouched() {
+    let record = JournalRecord::new();
+    let payload = message_to_payload(Some("plain text payload".into()));
+    let expected = Payload::TextPayload { text_payload: "plain text payload".into() };
+
+    assert_eq!(expected, enrich_payload(payload, &record),
+               "a payload with no relevant metadata fields should be returned untouched");
+}